@@ -1,6 +1,28 @@
-use super::{register, KeyRaw, KeyStatus, Version, KBD_ADDR};
+use super::{
+    register, Config, Debounce, Direction, FifoCount, IntStatus, KeyEvents, Keymap, KeyRaw,
+    KeyStatus, PollFrequency, Pull, SelfTestReport, Version, GPIO_COUNT, KBD_ADDR,
+};
 use embedded_hal_async::i2c::{I2c, Operation::*};
 
+/// The error type returned by GPIO operations on [`AsyncBbq10Kbd`]
+///
+/// Mirrors [`Error`](crate::Error), but wraps the caller's [`I2c::Error`]
+/// instead of assuming a concrete transport error, matching how every other
+/// method on this driver surfaces I2C failures directly as `I2C::Error`.
+#[derive(Debug)]
+pub enum GpioError<E> {
+    /// An underlying I2C transaction failed
+    I2c(E),
+    /// A GPIO pin number was out of range for the expander's [`GPIO_COUNT`] lines
+    InvalidPin,
+}
+
+impl<E> From<E> for GpioError<E> {
+    fn from(err: E) -> Self {
+        GpioError::I2c(err)
+    }
+}
+
 /// A struct representing an asynchronous driver for the BlackBerry Q10 PMOD
 /// Keyboard
 pub struct AsyncBbq10Kbd<I2C>
@@ -8,16 +30,33 @@ where
     I2C: I2c,
 {
     i2c: I2C,
+    address: u8,
 }
 
 impl<I2C> AsyncBbq10Kbd<I2C>
 where
     I2C: I2c,
 {
-    /// Create a new async BBQ10 Keyboard instance
+    /// Create a new async BBQ10 Keyboard instance at the default I2C address
     #[must_use]
     pub fn new(i2c: I2C) -> Self {
-        Self { i2c }
+        Self::new_with_address(i2c, KBD_ADDR)
+    }
+
+    /// Create a new async BBQ10 Keyboard instance at a given I2C address
+    ///
+    /// Use this if the keyboard's address has already been changed from the
+    /// default via [`AsyncBbq10Kbd::set_address`], e.g. to share a bus with
+    /// a second keyboard.
+    #[must_use]
+    pub fn new_with_address(i2c: I2C, address: u8) -> Self {
+        Self { i2c, address }
+    }
+
+    /// Get the I2C address this instance currently talks to
+    #[must_use]
+    pub fn address(&self) -> u8 {
+        self.address
     }
 
     /// Consume self, returning the inner I2C device
@@ -26,13 +65,31 @@ where
         self.i2c
     }
 
+    /// Change the keyboard's I2C address
+    ///
+    /// This writes the new address to the firmware's address-change
+    /// register, then updates the address stored in `self` to match. Future
+    /// calls on this instance will use the new address.
+    pub async fn set_address(&mut self, new: u8) -> Result<(), I2C::Error> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::ADDRESS_CHANGE | register::WRITE;
+        buf[1] = new;
+
+        self.i2c.write(self.address, &buf).await?;
+
+        self.address = new;
+
+        Ok(())
+    }
+
     /// Get the version reported by the keyboard's firmware
     pub async fn get_version(&mut self) -> Result<Version, I2C::Error> {
         let mut buf = [0u8; 1];
 
         self.i2c
             .transaction(
-                KBD_ADDR,
+                self.address,
                 &mut [Write(&[register::VERSION]), Read(&mut buf[..])],
             )
             .await?;
@@ -46,7 +103,7 @@ where
 
         self.i2c
             .transaction(
-                KBD_ADDR,
+                self.address,
                 &mut [Write(&[register::FIFO]), Read(&mut buf[..])],
             )
             .await?;
@@ -54,13 +111,52 @@ where
         Ok(KeyRaw::from_bytes(buf))
     }
 
+    /// Obtain a single fifo item, decoded through the given [`Keymap`]
+    ///
+    /// See [`keymap`](crate::keymap) for details on the decoding performed.
+    pub async fn get_fifo_key_event(
+        &mut self,
+        keymap: &mut Keymap<'_>,
+    ) -> Result<KeyEvents, I2C::Error> {
+        let raw = self.get_fifo_key_raw().await?;
+
+        Ok(keymap.decode(raw))
+    }
+
+    /// Drain the whole FIFO in one logical sweep into `buf`, returning the
+    /// number of events read
+    ///
+    /// See [`Bbq10Kbd::read_events`](crate::Bbq10Kbd::read_events) for the
+    /// draining strategy.
+    pub async fn read_events(&mut self, buf: &mut [KeyRaw]) -> Result<usize, I2C::Error> {
+        let available = match self.get_key_status().await?.fifo_count {
+            FifoCount::Known(n) => n as usize,
+            FifoCount::EmptyOr32 => 32,
+        };
+
+        let mut count = 0;
+
+        for slot in buf.iter_mut().take(available) {
+            let raw = self.get_fifo_key_raw().await?;
+
+            if raw == KeyRaw::Invalid {
+                break;
+            }
+
+            *slot = raw;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
     /// Get the current level of backlight. All u8 values are valid
     pub async fn get_backlight(&mut self) -> Result<u8, I2C::Error> {
         let mut buf = [0u8; 1];
 
         self.i2c
             .transaction(
-                KBD_ADDR,
+                self.address,
                 &mut [Write(&[register::BACKLIGHT]), Read(&mut buf[..])],
             )
             .await?;
@@ -75,7 +171,84 @@ where
         buf[0] = register::BACKLIGHT | register::WRITE;
         buf[1] = level;
 
-        self.i2c.write(KBD_ADDR, &buf).await
+        self.i2c.write(self.address, &buf).await
+    }
+
+    /// Get the current level of the secondary backlight. All u8 values are
+    /// valid
+    pub async fn get_backlight2(&mut self) -> Result<u8, I2C::Error> {
+        let mut buf = [0u8; 1];
+
+        self.i2c
+            .transaction(
+                self.address,
+                &mut [Write(&[register::BACKLIGHT2]), Read(&mut buf[..])],
+            )
+            .await?;
+
+        Ok(buf[0])
+    }
+
+    /// Set the current level of the secondary backlight. All u8 values are
+    /// valid
+    pub async fn set_backlight2(&mut self, level: u8) -> Result<(), I2C::Error> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::BACKLIGHT2 | register::WRITE;
+        buf[1] = level;
+
+        self.i2c.write(self.address, &buf).await
+    }
+
+    /// Get the current key debounce time
+    pub async fn get_debounce(&mut self) -> Result<Debounce, I2C::Error> {
+        let mut buf = [0u8; 1];
+
+        self.i2c
+            .transaction(
+                self.address,
+                &mut [Write(&[register::DEBOUNCE]), Read(&mut buf[..])],
+            )
+            .await?;
+
+        Ok(Debounce(buf[0]))
+    }
+
+    /// Set the key debounce time
+    pub async fn set_debounce(&mut self, debounce: Debounce) -> Result<(), I2C::Error> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::DEBOUNCE | register::WRITE;
+        buf[1] = debounce.0;
+
+        self.i2c.write(self.address, &buf).await
+    }
+
+    /// Get the current matrix scan poll frequency
+    pub async fn get_poll_frequency(&mut self) -> Result<PollFrequency, I2C::Error> {
+        let mut buf = [0u8; 1];
+
+        self.i2c
+            .transaction(
+                self.address,
+                &mut [Write(&[register::POLL_FREQUENCY]), Read(&mut buf[..])],
+            )
+            .await?;
+
+        Ok(PollFrequency(buf[0]))
+    }
+
+    /// Set the matrix scan poll frequency
+    pub async fn set_poll_frequency(
+        &mut self,
+        frequency: PollFrequency,
+    ) -> Result<(), I2C::Error> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::POLL_FREQUENCY | register::WRITE;
+        buf[1] = frequency.0;
+
+        self.i2c.write(self.address, &buf).await
     }
 
     /// Reset the device via software
@@ -88,7 +261,7 @@ where
         buf[0] = register::RESET;
 
         // This is enough to reset the device
-        self.i2c.write(KBD_ADDR, &buf).await
+        self.i2c.write(self.address, &buf).await
     }
 
     /// Get the reported status of the keyboard
@@ -97,11 +270,247 @@ where
 
         self.i2c
             .transaction(
-                KBD_ADDR,
+                self.address,
                 &mut [Write(&[register::KEY_STATUS]), Read(&mut buf[..])],
             )
             .await?;
 
         Ok(KeyStatus::from_byte(buf[0]))
     }
+
+    /// Get the keyboard's current interrupt/report configuration
+    pub async fn get_config(&mut self) -> Result<Config, I2C::Error> {
+        let mut buf = [0u8; 1];
+
+        self.i2c
+            .transaction(
+                self.address,
+                &mut [Write(&[register::CONFIG]), Read(&mut buf[..])],
+            )
+            .await?;
+
+        Ok(Config::from_byte(buf[0]))
+    }
+
+    /// Set the keyboard's interrupt/report configuration
+    pub async fn set_config(&mut self, config: Config) -> Result<(), I2C::Error> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::CONFIG | register::WRITE;
+        buf[1] = config.to_byte();
+
+        self.i2c.write(self.address, &buf).await
+    }
+
+    /// Get which condition(s) have latched the interrupt line
+    pub async fn get_int_status(&mut self) -> Result<IntStatus, I2C::Error> {
+        let mut buf = [0u8; 1];
+
+        self.i2c
+            .transaction(
+                self.address,
+                &mut [Write(&[register::INT_STATUS]), Read(&mut buf[..])],
+            )
+            .await?;
+
+        Ok(IntStatus::from_byte(buf[0]))
+    }
+
+    /// Clear the interrupt status register, de-asserting the IRQ line
+    ///
+    /// Call this only after draining the FIFO, or a key event queued
+    /// between the read and the clear may be lost.
+    pub async fn clear_int_status(&mut self) -> Result<(), I2C::Error> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::INT_STATUS | register::WRITE;
+        buf[1] = 0;
+
+        self.i2c.write(self.address, &buf).await
+    }
+
+    async fn gpio_read_byte(&mut self, reg: u8) -> Result<u8, I2C::Error> {
+        let mut buf = [0u8; 1];
+
+        self.i2c
+            .transaction(self.address, &mut [Write(&[reg]), Read(&mut buf[..])])
+            .await?;
+
+        Ok(buf[0])
+    }
+
+    async fn gpio_write_byte(&mut self, reg: u8, byte: u8) -> Result<(), I2C::Error> {
+        self.i2c.write(self.address, &[reg | register::WRITE, byte]).await
+    }
+
+    async fn gpio_read_bit(&mut self, reg: u8, pin: u8) -> Result<bool, GpioError<I2C::Error>> {
+        if pin >= GPIO_COUNT {
+            return Err(GpioError::InvalidPin);
+        }
+
+        Ok(self.gpio_read_byte(reg).await? & (1 << pin) != 0)
+    }
+
+    async fn gpio_write_bit(
+        &mut self,
+        reg: u8,
+        pin: u8,
+        value: bool,
+    ) -> Result<(), GpioError<I2C::Error>> {
+        if pin >= GPIO_COUNT {
+            return Err(GpioError::InvalidPin);
+        }
+
+        let mut byte = self.gpio_read_byte(reg).await?;
+
+        if value {
+            byte |= 1 << pin;
+        } else {
+            byte &= !(1 << pin);
+        }
+
+        Ok(self.gpio_write_byte(reg, byte).await?)
+    }
+
+    /// Set the direction of a single GPIO pin
+    ///
+    /// Returns [`GpioError::InvalidPin`] if `pin` is not less than [`GPIO_COUNT`].
+    pub async fn set_gpio_direction(
+        &mut self,
+        pin: u8,
+        direction: Direction,
+    ) -> Result<(), GpioError<I2C::Error>> {
+        self.gpio_write_bit(register::GPIO_DIR, pin, direction == Direction::Output)
+            .await
+    }
+
+    /// Set the pull resistor configuration of a single GPIO pin
+    ///
+    /// Returns [`GpioError::InvalidPin`] if `pin` is not less than [`GPIO_COUNT`].
+    pub async fn set_gpio_pull(&mut self, pin: u8, pull: Pull) -> Result<(), GpioError<I2C::Error>> {
+        match pull {
+            Pull::Disabled => self.gpio_write_bit(register::GPIO_PULL_EN, pin, false).await,
+            Pull::Up => {
+                self.gpio_write_bit(register::GPIO_PULL_EN, pin, true).await?;
+                self.gpio_write_bit(register::GPIO_PULL_DIR, pin, true).await
+            }
+            Pull::Down => {
+                self.gpio_write_bit(register::GPIO_PULL_EN, pin, true).await?;
+                self.gpio_write_bit(register::GPIO_PULL_DIR, pin, false).await
+            }
+        }
+    }
+
+    /// Enable or disable the GPIO interrupt for a single pin
+    ///
+    /// Returns [`GpioError::InvalidPin`] if `pin` is not less than [`GPIO_COUNT`].
+    pub async fn set_gpio_interrupt_enable(
+        &mut self,
+        pin: u8,
+        enable: bool,
+    ) -> Result<(), GpioError<I2C::Error>> {
+        self.gpio_write_bit(register::GPIO_INT_CONFIG, pin, enable)
+            .await
+    }
+
+    /// Get a bitmask of which GPIO pin(s) have latched their interrupt
+    pub async fn get_gpio_interrupt_status(&mut self) -> Result<u8, I2C::Error> {
+        self.gpio_read_byte(register::GPIO_INT_STATUS).await
+    }
+
+    /// Clear the GPIO interrupt status register
+    pub async fn clear_gpio_interrupt_status(&mut self) -> Result<(), I2C::Error> {
+        self.gpio_write_byte(register::GPIO_INT_STATUS, 0).await
+    }
+
+    /// Obtain a handle to a single GPIO pin
+    ///
+    /// The handle borrows the keyboard driver for as long as it is in use,
+    /// so only one pin can be manipulated at a time. There is no stable
+    /// `embedded-hal-async` digital pin trait to implement yet, so this
+    /// exposes the same operations as inherent async methods instead.
+    pub fn gpio_pin(&mut self, pin: u8) -> AsyncGpioPin<'_, I2C> {
+        AsyncGpioPin { kbd: self, pin }
+    }
+
+    /// Exercise a known round trip through the device, useful for verifying
+    /// wiring before relying on the keyboard
+    ///
+    /// This never returns an error on its own; instead, each check's result
+    /// is reported individually so a caller can see exactly what passed.
+    pub async fn self_test(&mut self) -> SelfTestReport {
+        let version_read = self.get_version().await.is_ok();
+
+        let backlight_roundtrip = match self.get_backlight().await {
+            Ok(original) => async {
+                self.set_backlight(original).await?;
+                Ok::<_, I2C::Error>(self.get_backlight().await? == original)
+            }
+            .await
+            .unwrap_or(false),
+            Err(_) => false,
+        };
+
+        let key_status = self.get_key_status().await;
+        let key_status_read = key_status.is_ok();
+        let fifo_count_sane = matches!(
+            key_status,
+            Ok(KeyStatus {
+                fifo_count: FifoCount::Known(0..=32) | FifoCount::EmptyOr32,
+                ..
+            })
+        );
+
+        SelfTestReport {
+            version_read,
+            backlight_roundtrip,
+            key_status_read,
+            fifo_count_sane,
+        }
+    }
+}
+
+/// A handle to a single GPIO pin on the async driver
+///
+/// See [`AsyncBbq10Kbd::gpio_pin`] for why this exposes inherent methods
+/// rather than implementing an `embedded-hal-async` digital pin trait.
+pub struct AsyncGpioPin<'a, I2C>
+where
+    I2C: I2c,
+{
+    kbd: &'a mut AsyncBbq10Kbd<I2C>,
+    pin: u8,
+}
+
+impl<'a, I2C> AsyncGpioPin<'a, I2C>
+where
+    I2C: I2c,
+{
+    pub async fn is_high(&mut self) -> Result<bool, GpioError<I2C::Error>> {
+        self.kbd.gpio_read_bit(register::GPIO_VALUE, self.pin).await
+    }
+
+    pub async fn is_low(&mut self) -> Result<bool, GpioError<I2C::Error>> {
+        Ok(!self.is_high().await?)
+    }
+
+    pub async fn set_high(&mut self) -> Result<(), GpioError<I2C::Error>> {
+        self.kbd
+            .gpio_write_bit(register::GPIO_VALUE, self.pin, true)
+            .await
+    }
+
+    pub async fn set_low(&mut self) -> Result<(), GpioError<I2C::Error>> {
+        self.kbd
+            .gpio_write_bit(register::GPIO_VALUE, self.pin, false)
+            .await
+    }
+
+    pub async fn is_set_high(&mut self) -> Result<bool, GpioError<I2C::Error>> {
+        self.is_high().await
+    }
+
+    pub async fn is_set_low(&mut self) -> Result<bool, GpioError<I2C::Error>> {
+        self.is_low().await
+    }
 }
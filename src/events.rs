@@ -0,0 +1,191 @@
+//! A buffered, evdev-like batch event interface, with synthesized key-repeat
+//!
+//! [`Bbq10Kbd::read_events`](crate::Bbq10Kbd::read_events) (and its async
+//! equivalent) drain the whole FIFO in one logical sweep instead of making
+//! the caller pull one key at a time. [`EventReader`] sits on top of that
+//! and synthesizes repeated `Pressed` events for a held key, the same
+//! initial-delay + repeat-rate autorepeat model used by the Linux input
+//! layer.
+
+use crate::KeyRaw;
+
+/// The autorepeat timing for an [`EventReader`], in caller-defined time units
+/// (typically milliseconds)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RepeatConfig {
+    /// Delay from the initial press before autorepeat begins
+    pub initial_delay: u32,
+    /// Delay between each subsequent synthesized repeat
+    pub repeat_interval: u32,
+}
+
+impl Default for RepeatConfig {
+    /// 500 unit initial delay, 50 unit repeat interval
+    fn default() -> Self {
+        Self {
+            initial_delay: 500,
+            repeat_interval: 50,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct HeldKey {
+    code: u8,
+    next_repeat_at: u32,
+}
+
+/// Tracks the currently-held key and synthesizes repeat `Pressed` events
+///
+/// This has no knowledge of real time; the caller passes in its own
+/// monotonically increasing tick/elapsed-time counter each call.
+#[derive(Copy, Clone, Debug)]
+pub struct EventReader {
+    config: RepeatConfig,
+    held: Option<HeldKey>,
+}
+
+impl EventReader {
+    /// Create a new reader with the given repeat timing
+    pub const fn new(config: RepeatConfig) -> Self {
+        Self {
+            config,
+            held: None,
+        }
+    }
+
+    /// Feed a batch of freshly read raw events (see
+    /// [`Bbq10Kbd::read_events`](crate::Bbq10Kbd::read_events)) at the given
+    /// time, writing the input events interleaved with any synthesized
+    /// repeats into `out`
+    ///
+    /// Returns the number of events written to `out`. If `out` fills up,
+    /// input events that do not fit are dropped, while synthesized repeats
+    /// that do not fit stay pending and are emitted by a later call.
+    pub fn process(&mut self, events: &[KeyRaw], now: u32, out: &mut [KeyRaw]) -> usize {
+        let mut n = 0;
+
+        for &event in events {
+            match event {
+                KeyRaw::Pressed(code) => {
+                    self.held = Some(HeldKey {
+                        code,
+                        next_repeat_at: now.wrapping_add(self.config.initial_delay),
+                    });
+                }
+                KeyRaw::Released(code) => {
+                    if matches!(self.held, Some(held) if held.code == code) {
+                        self.held = None;
+                    }
+                }
+                KeyRaw::Held(_) | KeyRaw::Invalid => {}
+            }
+
+            if let Some(slot) = out.get_mut(n) {
+                *slot = event;
+                n += 1;
+            }
+        }
+
+        let repeat_interval = self.config.repeat_interval.max(1);
+        while let Some(held) = &mut self.held {
+            if now.wrapping_sub(held.next_repeat_at) >= u32::MAX / 2 {
+                break;
+            }
+
+            let Some(slot) = out.get_mut(n) else {
+                break;
+            };
+
+            *slot = KeyRaw::Pressed(held.code);
+            n += 1;
+            held.next_repeat_at = held.next_repeat_at.wrapping_add(repeat_interval);
+        }
+
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: RepeatConfig = RepeatConfig {
+        initial_delay: 100,
+        repeat_interval: 10,
+    };
+
+    #[test]
+    fn no_repeat_before_initial_delay_elapses() {
+        let mut reader = EventReader::new(CONFIG);
+        let mut out = [KeyRaw::Invalid; 4];
+
+        let n = reader.process(&[KeyRaw::Pressed(5)], 0, &mut out);
+        assert_eq!(&out[..n], &[KeyRaw::Pressed(5)]);
+
+        let n = reader.process(&[], 99, &mut out);
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn repeat_fires_after_initial_delay_then_every_interval() {
+        let mut reader = EventReader::new(CONFIG);
+        let mut out = [KeyRaw::Invalid; 4];
+
+        reader.process(&[KeyRaw::Pressed(5)], 0, &mut out);
+
+        let n = reader.process(&[], 100, &mut out);
+        assert_eq!(&out[..n], &[KeyRaw::Pressed(5)]);
+
+        let n = reader.process(&[], 109, &mut out);
+        assert_eq!(n, 0);
+
+        let n = reader.process(&[], 110, &mut out);
+        assert_eq!(&out[..n], &[KeyRaw::Pressed(5)]);
+    }
+
+    #[test]
+    fn released_cancels_further_repeats() {
+        let mut reader = EventReader::new(CONFIG);
+        let mut out = [KeyRaw::Invalid; 4];
+
+        reader.process(&[KeyRaw::Pressed(5)], 0, &mut out);
+        reader.process(&[KeyRaw::Released(5)], 50, &mut out);
+
+        let n = reader.process(&[], 100, &mut out);
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn release_of_a_different_code_does_not_cancel_repeat() {
+        let mut reader = EventReader::new(CONFIG);
+        let mut out = [KeyRaw::Invalid; 4];
+
+        reader.process(&[KeyRaw::Pressed(5)], 0, &mut out);
+        reader.process(&[KeyRaw::Released(6)], 50, &mut out);
+
+        let n = reader.process(&[], 100, &mut out);
+        assert_eq!(&out[..n], &[KeyRaw::Pressed(5)]);
+    }
+
+    #[test]
+    fn multiple_pending_repeats_fill_out_buffer_in_one_call() {
+        let mut reader = EventReader::new(CONFIG);
+        let mut out = [KeyRaw::Invalid; 4];
+
+        reader.process(&[KeyRaw::Pressed(5)], 0, &mut out);
+
+        // The initial delay plus three repeat intervals have elapsed, so
+        // four synthesized repeats should be produced in a single call.
+        let n = reader.process(&[], 130, &mut out);
+        assert_eq!(
+            &out[..n],
+            &[
+                KeyRaw::Pressed(5),
+                KeyRaw::Pressed(5),
+                KeyRaw::Pressed(5),
+                KeyRaw::Pressed(5)
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! GPIO expander support for the firmware's spare IO bank
+//!
+//! The Q10 firmware exposes eight general-purpose IO lines alongside the
+//! keyboard matrix, backed by registers 0x0B..=0x10. This module reads and
+//! writes those registers and hands out per-pin handles implementing the
+//! `embedded-hal` digital pin traits, so the spare lines (LEDs, buttons,
+//! whatever the PMOD breaks out) can be driven through the same I2C driver
+//! already in use for keys.
+
+use crate::{register, Bbq10Kbd, Error, Result};
+use embedded_hal::blocking::i2c::{Read, Write};
+use embedded_hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin};
+
+/// The number of GPIO lines exposed by the expander bank
+pub const GPIO_COUNT: u8 = 8;
+
+/// The direction of a single GPIO pin
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+/// The pull resistor configuration of a single GPIO pin
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Pull {
+    Disabled,
+    Up,
+    Down,
+}
+
+impl<I2C> Bbq10Kbd<I2C>
+where
+    I2C: Read + Write,
+{
+    fn gpio_read_byte(&mut self, reg: u8) -> Result<u8> {
+        let mut buf = [0u8; 1];
+
+        buf[0] = reg;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
+
+        buf[0] = 0;
+
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
+
+        Ok(buf[0])
+    }
+
+    fn gpio_write_byte(&mut self, reg: u8, byte: u8) -> Result<()> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = reg | register::WRITE;
+        buf[1] = byte;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)
+    }
+
+    fn gpio_read_bit(&mut self, reg: u8, pin: u8) -> Result<bool> {
+        if pin >= GPIO_COUNT {
+            return Err(Error::InvalidPin);
+        }
+
+        Ok(self.gpio_read_byte(reg)? & (1 << pin) != 0)
+    }
+
+    fn gpio_write_bit(&mut self, reg: u8, pin: u8, value: bool) -> Result<()> {
+        if pin >= GPIO_COUNT {
+            return Err(Error::InvalidPin);
+        }
+
+        let mut byte = self.gpio_read_byte(reg)?;
+
+        if value {
+            byte |= 1 << pin;
+        } else {
+            byte &= !(1 << pin);
+        }
+
+        self.gpio_write_byte(reg, byte)
+    }
+
+    /// Set the direction of a single GPIO pin
+    ///
+    /// Returns [`Error::InvalidPin`] if `pin` is not less than [`GPIO_COUNT`].
+    pub fn set_gpio_direction(&mut self, pin: u8, direction: Direction) -> Result<()> {
+        self.gpio_write_bit(register::GPIO_DIR, pin, direction == Direction::Output)
+    }
+
+    /// Set the pull resistor configuration of a single GPIO pin
+    ///
+    /// Returns [`Error::InvalidPin`] if `pin` is not less than [`GPIO_COUNT`].
+    pub fn set_gpio_pull(&mut self, pin: u8, pull: Pull) -> Result<()> {
+        match pull {
+            Pull::Disabled => self.gpio_write_bit(register::GPIO_PULL_EN, pin, false),
+            Pull::Up => {
+                self.gpio_write_bit(register::GPIO_PULL_EN, pin, true)?;
+                self.gpio_write_bit(register::GPIO_PULL_DIR, pin, true)
+            }
+            Pull::Down => {
+                self.gpio_write_bit(register::GPIO_PULL_EN, pin, true)?;
+                self.gpio_write_bit(register::GPIO_PULL_DIR, pin, false)
+            }
+        }
+    }
+
+    /// Enable or disable the GPIO interrupt for a single pin
+    ///
+    /// Returns [`Error::InvalidPin`] if `pin` is not less than [`GPIO_COUNT`].
+    pub fn set_gpio_interrupt_enable(&mut self, pin: u8, enable: bool) -> Result<()> {
+        self.gpio_write_bit(register::GPIO_INT_CONFIG, pin, enable)
+    }
+
+    /// Get a bitmask of which GPIO pin(s) have latched their interrupt
+    pub fn get_gpio_interrupt_status(&mut self) -> Result<u8> {
+        self.gpio_read_byte(register::GPIO_INT_STATUS)
+    }
+
+    /// Clear the GPIO interrupt status register
+    pub fn clear_gpio_interrupt_status(&mut self) -> Result<()> {
+        self.gpio_write_byte(register::GPIO_INT_STATUS, 0)
+    }
+
+    /// Obtain a handle to a single GPIO pin
+    ///
+    /// The handle borrows the keyboard driver for as long as it is in use,
+    /// so only one pin can be manipulated at a time.
+    pub fn gpio_pin(&mut self, pin: u8) -> GpioPin<'_, I2C> {
+        GpioPin {
+            kbd: core::cell::RefCell::new(self),
+            pin,
+        }
+    }
+}
+
+/// A handle to a single GPIO pin, implementing the `embedded-hal` digital
+/// pin traits
+///
+/// `InputPin`/`StatefulOutputPin` take `&self`, but reading the expander
+/// still requires an I2C transaction, so the borrow of the keyboard driver
+/// is wrapped in a `RefCell` to provide that interior mutability.
+pub struct GpioPin<'a, I2C>
+where
+    I2C: Read + Write,
+{
+    kbd: core::cell::RefCell<&'a mut Bbq10Kbd<I2C>>,
+    pin: u8,
+}
+
+impl<'a, I2C> InputPin for GpioPin<'a, I2C>
+where
+    I2C: Read + Write,
+{
+    type Error = Error;
+
+    fn is_high(&self) -> core::result::Result<bool, Error> {
+        self.kbd
+            .borrow_mut()
+            .gpio_read_bit(register::GPIO_VALUE, self.pin)
+    }
+
+    fn is_low(&self) -> core::result::Result<bool, Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+impl<'a, I2C> OutputPin for GpioPin<'a, I2C>
+where
+    I2C: Read + Write,
+{
+    type Error = Error;
+
+    fn set_low(&mut self) -> core::result::Result<(), Error> {
+        self.kbd
+            .get_mut()
+            .gpio_write_bit(register::GPIO_VALUE, self.pin, false)
+    }
+
+    fn set_high(&mut self) -> core::result::Result<(), Error> {
+        self.kbd
+            .get_mut()
+            .gpio_write_bit(register::GPIO_VALUE, self.pin, true)
+    }
+}
+
+impl<'a, I2C> StatefulOutputPin for GpioPin<'a, I2C>
+where
+    I2C: Read + Write,
+{
+    fn is_set_high(&self) -> core::result::Result<bool, Error> {
+        self.is_high()
+    }
+
+    fn is_set_low(&self) -> core::result::Result<bool, Error> {
+        self.is_low()
+    }
+}
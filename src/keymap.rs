@@ -0,0 +1,558 @@
+//! Decoding of raw FIFO key codes into printable characters and named keys
+//!
+//! The firmware's FIFO only ever reports a raw code plus a press/hold/release
+//! state ([`KeyRaw`]). This module layers a small state machine on top of
+//! that stream, modeled loosely on the Linux console driver's `keyboard.c`:
+//! it tracks which modifiers are currently active (including "sticky"
+//! single-tap and double-tap-latch behavior), looks printable codes up in a
+//! caller-supplied layer table, and composes dead-key (diacritical) sequences.
+
+use crate::KeyRaw;
+
+/// Raw firmware key codes that do not represent a printable character
+///
+/// These mirror the special-purpose codes the Q10 firmware reserves outside
+/// of the printable range; see [`register`](crate::register) for the
+/// equivalent convention used for I2C registers.
+pub(crate) mod keycode {
+    pub(crate) const BACKSPACE: u8 = 0x08;
+    pub(crate) const TAB: u8 = 0x09;
+    pub(crate) const ENTER: u8 = 0x0A;
+    pub(crate) const ESCAPE: u8 = 0x1B;
+
+    pub(crate) const LEFT: u8 = 0xB4;
+    pub(crate) const UP: u8 = 0xB5;
+    pub(crate) const DOWN: u8 = 0xB6;
+    pub(crate) const RIGHT: u8 = 0xB7;
+
+    pub(crate) const LEFT_ALT: u8 = 0xD1;
+    pub(crate) const SYM: u8 = 0xD2;
+    pub(crate) const LEFT_SHIFT: u8 = 0xD3;
+    pub(crate) const RIGHT_SHIFT: u8 = 0xD4;
+    pub(crate) const CTRL: u8 = 0xD5;
+}
+
+/// A modifier key tracked by the [`Keymap`] state machine
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Modifier {
+    Shift,
+    Alt,
+    Sym,
+    Ctrl,
+}
+
+/// A non-printable key recognized by the keymap layer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NamedKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Modifier(Modifier),
+}
+
+/// A single decoded key, either a composed printable character or a named key
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// A printable character, already composed with any active modifiers
+    /// and/or a preceding dead key
+    Char(char),
+    /// A non-printable or modifier key
+    Named(NamedKey),
+}
+
+/// The press/hold/release state of a decoded [`Key`]
+///
+/// This mirrors the `Pressed`/`Held`/`Released` states of [`KeyRaw`]
+/// one-for-one (`KeyRaw::Invalid` decodes to no event), but is named
+/// `Action` here since it is now attached to a decoded [`Key`] rather than a
+/// raw code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Pressed,
+    Held,
+    Released,
+}
+
+/// A fully decoded key event, ready for application-level consumption
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub action: Action,
+}
+
+/// The active layer of the [`LayerTable`], selected by which modifier (if
+/// any) is currently asserted
+///
+/// When more than one modifier is active, `Sym` takes priority over `Alt`
+/// over `Shift`, matching the firmware's own precedence for its built-in
+/// layers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Layer {
+    Base = 0,
+    Shift = 1,
+    Alt = 2,
+    Sym = 3,
+}
+
+/// The number of layers in a [`LayerTable`]
+pub const LAYER_COUNT: usize = 4;
+
+/// A table mapping a raw printable code (0..=127) and active [`Layer`] to a
+/// composed character
+///
+/// Entries for codes with no meaning on a given layer should be set to
+/// `'\0'`; [`Keymap::decode`] produces no event at all for such a code.
+pub type LayerTable = [[char; 128]; LAYER_COUNT];
+
+/// A single dead-key composition: `(dead, base) -> composed`
+///
+/// For example `('\'', 'e', 'é')` composes an acute accent with `e`.
+pub type DeadKeyEntry = (char, char, char);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum StickyState {
+    Inactive,
+    /// Applies to exactly the next non-modifier key, then clears
+    Tapped,
+    /// Applies until this modifier is tapped again
+    Latched,
+}
+
+impl StickyState {
+    fn tap(self) -> Self {
+        match self {
+            StickyState::Inactive => StickyState::Tapped,
+            StickyState::Tapped => StickyState::Latched,
+            StickyState::Latched => StickyState::Inactive,
+        }
+    }
+
+    fn is_active(self) -> bool {
+        !matches!(self, StickyState::Inactive)
+    }
+}
+
+/// The sticky state of all four modifiers tracked by a [`Keymap`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct ModifierStates {
+    shift: StickyState,
+    alt: StickyState,
+    sym: StickyState,
+    ctrl: StickyState,
+}
+
+impl ModifierStates {
+    const fn new() -> Self {
+        Self {
+            shift: StickyState::Inactive,
+            alt: StickyState::Inactive,
+            sym: StickyState::Inactive,
+            ctrl: StickyState::Inactive,
+        }
+    }
+
+    fn get(&self, m: Modifier) -> StickyState {
+        match m {
+            Modifier::Shift => self.shift,
+            Modifier::Alt => self.alt,
+            Modifier::Sym => self.sym,
+            Modifier::Ctrl => self.ctrl,
+        }
+    }
+
+    fn set(&mut self, m: Modifier, state: StickyState) {
+        match m {
+            Modifier::Shift => self.shift = state,
+            Modifier::Alt => self.alt = state,
+            Modifier::Sym => self.sym = state,
+            Modifier::Ctrl => self.ctrl = state,
+        }
+    }
+
+    fn layer(&self) -> Layer {
+        if self.sym.is_active() {
+            Layer::Sym
+        } else if self.alt.is_active() {
+            Layer::Alt
+        } else if self.shift.is_active() {
+            Layer::Shift
+        } else {
+            Layer::Base
+        }
+    }
+
+    /// Clear every modifier that only applied to a single tap
+    fn consume_tapped(&mut self) {
+        for m in [Modifier::Shift, Modifier::Alt, Modifier::Sym, Modifier::Ctrl] {
+            if self.get(m) == StickyState::Tapped {
+                self.set(m, StickyState::Inactive);
+            }
+        }
+    }
+}
+
+/// Up to two decoded [`KeyEvent`]s, yielded for a single raw FIFO item
+///
+/// More than one event is only ever produced when a buffered dead key turns
+/// out not to compose with the following key, in which case both the dead
+/// key and the following key are emitted as separate [`Key::Char`] events.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KeyEvents {
+    first: Option<KeyEvent>,
+    second: Option<KeyEvent>,
+}
+
+impl Iterator for KeyEvents {
+    type Item = KeyEvent;
+
+    fn next(&mut self) -> Option<KeyEvent> {
+        self.first.take().or_else(|| self.second.take())
+    }
+}
+
+/// A dead key buffered by [`Keymap`], waiting to see the next base character
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct PendingDead {
+    /// The raw code of the key that produced `dead`, so its later `Held`/
+    /// `Released` items can be recognized and suppressed while the
+    /// composition is still outstanding
+    code: u8,
+    dead: char,
+}
+
+/// Decodes the raw FIFO stream into [`KeyEvent`]s
+///
+/// A `Keymap` owns the modifier/sticky and dead-key state, so it must be fed
+/// every raw item in order (nothing should be dropped) to stay in sync. It is
+/// `Copy`-cheap enough to construct once and reuse for the lifetime of the
+/// driver, and has no dependency on either the blocking or async driver
+/// structs so it can be used with both.
+#[derive(Copy, Clone)]
+pub struct Keymap<'a> {
+    layers: &'a LayerTable,
+    dead_keys: &'a [DeadKeyEntry],
+    modifiers: ModifierStates,
+    pending_dead: Option<PendingDead>,
+}
+
+impl<'a> Keymap<'a> {
+    /// Create a new keymap from a caller-supplied layer table and dead-key
+    /// composition table
+    pub const fn new(layers: &'a LayerTable, dead_keys: &'a [DeadKeyEntry]) -> Self {
+        Self {
+            layers,
+            dead_keys,
+            modifiers: ModifierStates::new(),
+            pending_dead: None,
+        }
+    }
+
+    fn modifier_for_code(code: u8) -> Option<Modifier> {
+        match code {
+            keycode::LEFT_SHIFT | keycode::RIGHT_SHIFT => Some(Modifier::Shift),
+            keycode::LEFT_ALT => Some(Modifier::Alt),
+            keycode::SYM => Some(Modifier::Sym),
+            keycode::CTRL => Some(Modifier::Ctrl),
+            _ => None,
+        }
+    }
+
+    fn named_for_code(code: u8) -> Option<NamedKey> {
+        match code {
+            keycode::UP => Some(NamedKey::Up),
+            keycode::DOWN => Some(NamedKey::Down),
+            keycode::LEFT => Some(NamedKey::Left),
+            keycode::RIGHT => Some(NamedKey::Right),
+            keycode::ENTER => Some(NamedKey::Enter),
+            keycode::BACKSPACE => Some(NamedKey::Backspace),
+            keycode::TAB => Some(NamedKey::Tab),
+            keycode::ESCAPE => Some(NamedKey::Escape),
+            _ => None,
+        }
+    }
+
+    fn lookup_char(&self, code: u8) -> Option<char> {
+        let row = self.layers[self.modifiers.layer() as usize];
+        match row.get(code as usize) {
+            Some(&'\0') | None => None,
+            Some(&c) => Some(c),
+        }
+    }
+
+    fn is_dead_key(&self, c: char) -> bool {
+        self.dead_keys.iter().any(|&(d, _, _)| d == c)
+    }
+
+    fn compose(&mut self, code: u8, base: char) -> KeyEvents {
+        let pressed = |key| KeyEvent {
+            key,
+            action: Action::Pressed,
+        };
+
+        if let Some(pending) = self.pending_dead.take() {
+            if let Some(&(_, _, composed)) = self
+                .dead_keys
+                .iter()
+                .find(|&&(d, b, _)| d == pending.dead && b == base)
+            {
+                return KeyEvents {
+                    first: Some(pressed(Key::Char(composed))),
+                    second: None,
+                };
+            }
+
+            // No composition exists for this pair: the buffered dead key is
+            // emitted as a literal character, and `base` is handled exactly
+            // as it would be with no dead key pending -- including starting
+            // a brand new pending composition of its own.
+            if self.is_dead_key(base) {
+                self.pending_dead = Some(PendingDead { code, dead: base });
+
+                return KeyEvents {
+                    first: Some(pressed(Key::Char(pending.dead))),
+                    second: None,
+                };
+            }
+
+            return KeyEvents {
+                first: Some(pressed(Key::Char(pending.dead))),
+                second: Some(pressed(Key::Char(base))),
+            };
+        }
+
+        if self.is_dead_key(base) {
+            self.pending_dead = Some(PendingDead { code, dead: base });
+            return KeyEvents::default();
+        }
+
+        KeyEvents {
+            first: Some(pressed(Key::Char(base))),
+            second: None,
+        }
+    }
+
+    /// Decode a single raw FIFO item into zero, one, or two [`KeyEvent`]s
+    pub fn decode(&mut self, raw: KeyRaw) -> KeyEvents {
+        let (code, action) = match raw {
+            KeyRaw::Invalid => return KeyEvents::default(),
+            KeyRaw::Pressed(n) => (n, Action::Pressed),
+            KeyRaw::Held(n) => (n, Action::Held),
+            KeyRaw::Released(n) => (n, Action::Released),
+        };
+
+        if let Some(modifier) = Self::modifier_for_code(code) {
+            if action == Action::Pressed {
+                let next = self.modifiers.get(modifier).tap();
+                self.modifiers.set(modifier, next);
+            }
+
+            return KeyEvents {
+                first: Some(KeyEvent {
+                    key: Key::Named(NamedKey::Modifier(modifier)),
+                    action,
+                }),
+                second: None,
+            };
+        }
+
+        if let Some(named) = Self::named_for_code(code) {
+            if action == Action::Pressed {
+                self.modifiers.consume_tapped();
+            }
+
+            return KeyEvents {
+                first: Some(KeyEvent {
+                    key: Key::Named(named),
+                    action,
+                }),
+                second: None,
+            };
+        }
+
+        let Some(base) = self.lookup_char(code) else {
+            return KeyEvents::default();
+        };
+
+        if action != Action::Pressed {
+            // A dead key that hasn't composed yet is never surfaced as its
+            // own character, so its Held/Released items must be swallowed
+            // too, not just its Pressed item.
+            if matches!(self.pending_dead, Some(pending) if pending.code == code) {
+                return KeyEvents::default();
+            }
+
+            return KeyEvents {
+                first: Some(KeyEvent {
+                    key: Key::Char(base),
+                    action,
+                }),
+                second: None,
+            };
+        }
+
+        let events = self.compose(code, base);
+        self.modifiers.consume_tapped();
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_LAYER: [char; 128] = ['\0'; 128];
+
+    fn layers_with(base: &[(u8, char)]) -> LayerTable {
+        let mut layers = [EMPTY_LAYER; LAYER_COUNT];
+        for &(code, c) in base {
+            layers[Layer::Base as usize][code as usize] = c;
+        }
+        layers
+    }
+
+    fn char_event(key: KeyEvents, c: char, action: Action) {
+        let mut key = key;
+        assert_eq!(
+            key.next(),
+            Some(KeyEvent {
+                key: Key::Char(c),
+                action
+            })
+        );
+        assert_eq!(key.next(), None);
+    }
+
+    #[test]
+    fn plain_key_roundtrips_press_hold_release() {
+        let layers = layers_with(&[(0x1E, 'e')]);
+        let mut keymap = Keymap::new(&layers, &[]);
+
+        char_event(keymap.decode(KeyRaw::Pressed(0x1E)), 'e', Action::Pressed);
+        char_event(keymap.decode(KeyRaw::Held(0x1E)), 'e', Action::Held);
+        char_event(
+            keymap.decode(KeyRaw::Released(0x1E)),
+            'e',
+            Action::Released,
+        );
+    }
+
+    #[test]
+    fn dead_key_composes_with_following_base() {
+        let layers = layers_with(&[(0x28, '\''), (0x1E, 'e')]);
+        let dead_keys = [('\'', 'e', 'é')];
+        let mut keymap = Keymap::new(&layers, &dead_keys);
+
+        assert_eq!(keymap.decode(KeyRaw::Pressed(0x28)).count(), 0);
+        assert_eq!(keymap.decode(KeyRaw::Held(0x28)).count(), 0);
+        assert_eq!(keymap.decode(KeyRaw::Released(0x28)).count(), 0);
+
+        char_event(
+            keymap.decode(KeyRaw::Pressed(0x1E)),
+            'é',
+            Action::Pressed,
+        );
+    }
+
+    #[test]
+    fn dead_key_held_while_pending_emits_nothing() {
+        let layers = layers_with(&[(0x28, '\''), (0x1E, 'e')]);
+        let dead_keys = [('\'', 'e', 'é')];
+        let mut keymap = Keymap::new(&layers, &dead_keys);
+
+        assert_eq!(keymap.decode(KeyRaw::Pressed(0x28)).count(), 0);
+        // Holding the accent key before releasing it must not leak a bare
+        // `'` character into the stream.
+        assert_eq!(keymap.decode(KeyRaw::Held(0x28)).count(), 0);
+        assert_eq!(keymap.decode(KeyRaw::Held(0x28)).count(), 0);
+        assert_eq!(keymap.decode(KeyRaw::Released(0x28)).count(), 0);
+
+        char_event(
+            keymap.decode(KeyRaw::Pressed(0x1E)),
+            'é',
+            Action::Pressed,
+        );
+    }
+
+    #[test]
+    fn dead_key_with_no_composition_emits_both_chars() {
+        let layers = layers_with(&[(0x28, '\''), (0x16, 'z')]);
+        let dead_keys = [('\'', 'e', 'é')];
+        let mut keymap = Keymap::new(&layers, &dead_keys);
+
+        assert_eq!(keymap.decode(KeyRaw::Pressed(0x28)).count(), 0);
+
+        let mut events = keymap.decode(KeyRaw::Pressed(0x16));
+        assert_eq!(
+            events.next(),
+            Some(KeyEvent {
+                key: Key::Char('\''),
+                action: Action::Pressed
+            })
+        );
+        assert_eq!(
+            events.next(),
+            Some(KeyEvent {
+                key: Key::Char('z'),
+                action: Action::Pressed
+            })
+        );
+        assert_eq!(events.next(), None);
+    }
+
+    #[test]
+    fn chained_dead_keys_start_a_new_pending_composition() {
+        let layers = layers_with(&[(0x28, '\''), (0x34, '`'), (0x1E, 'e')]);
+        let dead_keys = [('\'', 'e', 'é'), ('`', 'e', 'è')];
+        let mut keymap = Keymap::new(&layers, &dead_keys);
+
+        // `'` has no composition with the following dead key `` ` ``, so
+        // `'` is emitted literally, but `` ` `` becomes the new pending
+        // dead key rather than being emitted as well.
+        assert_eq!(keymap.decode(KeyRaw::Pressed(0x28)).count(), 0);
+        char_event(
+            keymap.decode(KeyRaw::Pressed(0x34)),
+            '\'',
+            Action::Pressed,
+        );
+
+        // The second dead key still composes correctly with what follows.
+        char_event(
+            keymap.decode(KeyRaw::Pressed(0x1E)),
+            'è',
+            Action::Pressed,
+        );
+    }
+
+    #[test]
+    fn sticky_modifier_single_tap_applies_once() {
+        let layers = layers_with(&[(0x1E, 'a')]);
+        let mut layers = layers;
+        layers[Layer::Shift as usize][0x1E] = 'A';
+        let mut keymap = Keymap::new(&layers, &[]);
+
+        keymap.decode(KeyRaw::Pressed(keycode::LEFT_SHIFT));
+        char_event(keymap.decode(KeyRaw::Pressed(0x1E)), 'A', Action::Pressed);
+        // The tap was consumed by the previous key, so the next one is
+        // back to the base layer.
+        char_event(keymap.decode(KeyRaw::Pressed(0x1E)), 'a', Action::Pressed);
+    }
+
+    #[test]
+    fn sticky_modifier_double_tap_latches() {
+        let mut layers = layers_with(&[(0x1E, 'a')]);
+        layers[Layer::Shift as usize][0x1E] = 'A';
+        let mut keymap = Keymap::new(&layers, &[]);
+
+        keymap.decode(KeyRaw::Pressed(keycode::LEFT_SHIFT));
+        keymap.decode(KeyRaw::Pressed(keycode::LEFT_SHIFT));
+
+        char_event(keymap.decode(KeyRaw::Pressed(0x1E)), 'A', Action::Pressed);
+        // Latched, so it stays active for a second key too.
+        char_event(keymap.decode(KeyRaw::Pressed(0x1E)), 'A', Action::Pressed);
+    }
+}
@@ -18,9 +18,22 @@ use embedded_hal::blocking::i2c::{Read, Write};
 #[cfg(feature = "embedded-hal-async")]
 mod r#async;
 #[cfg(feature = "embedded-hal-async")]
-pub use r#async::AsyncBbq10Kbd;
+pub use r#async::{AsyncBbq10Kbd, AsyncGpioPin, GpioError};
 
-// DEFAULT ADDRESS, not currently changeable
+mod keymap;
+pub use keymap::{
+    Action, DeadKeyEntry, Key, KeyEvent, KeyEvents, Keymap, LayerTable, Modifier, NamedKey,
+    LAYER_COUNT,
+};
+
+mod gpio;
+pub use gpio::{Direction, GpioPin, Pull, GPIO_COUNT};
+
+mod events;
+pub use events::{EventReader, RepeatConfig};
+
+// Default I2C address; see `set_address`/`new_with_address` to use a
+// different address (e.g. after an `ADDRESS_CHANGE` register write).
 const KBD_ADDR: u8 = 0x1F;
 
 /// The Error type for this crate
@@ -28,6 +41,8 @@ const KBD_ADDR: u8 = 0x1F;
 pub enum Error {
     /// A generic embedded-hal I2C error
     I2c,
+    /// A GPIO pin number was out of range for the expander's [`GPIO_COUNT`](crate::GPIO_COUNT) lines
+    InvalidPin,
 }
 
 /// The Result type for this crate
@@ -39,6 +54,7 @@ where
     I2C: Read + Write,
 {
     i2c: I2C,
+    address: u8,
 }
 
 /// The version identifier of our keyboard's firmware
@@ -105,24 +121,147 @@ pub struct KeyStatus {
     pub fifo_count: FifoCount,
 }
 
+/// The keyboard's interrupt and reporting configuration (register 0x02)
+///
+/// When `key_interrupt` (or any of the other `*_interrupt` fields) is set,
+/// the firmware drives its hardware IRQ line for the matching condition,
+/// letting a host wire the keyboard up like any other interrupt-driven
+/// peripheral instead of busy-polling [`Bbq10Kbd::get_fifo_key_raw`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Drive the IRQ line when a key event is queued to the FIFO
+    pub key_interrupt: bool,
+    /// Drive the IRQ line when the FIFO overflows
+    pub overflow_interrupt: bool,
+    /// Drive the IRQ line on a CapsLock state change
+    pub capslock_interrupt: bool,
+    /// Drive the IRQ line on a NumLock state change
+    pub numlock_interrupt: bool,
+    /// Drive the IRQ line if the firmware panics
+    pub panic_interrupt: bool,
+    /// Report raw scan codes rather than firmware-decoded ASCII
+    pub raw_report_mode: bool,
+    /// Report modifier keys as their own FIFO events instead of applying
+    /// them in firmware before an event is queued
+    pub use_modifiers: bool,
+}
+
+/// The key debounce time, in firmware scan ticks
+///
+/// Higher values reject more contact bounce at the cost of added input
+/// latency.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Debounce(pub u8);
+
+/// The keyboard matrix scan poll frequency, in Hz
+///
+/// Higher values lower input latency at the cost of added I2C traffic and
+/// firmware CPU time.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PollFrequency(pub u8);
+
+/// Which condition(s) latched the interrupt status register (0x03)
+///
+/// Read this after observing the IRQ line assert, then call
+/// [`Bbq10Kbd::clear_int_status`] once the FIFO has been drained.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IntStatus {
+    pub key: bool,
+    pub overflow: bool,
+    pub capslock: bool,
+    pub numlock: bool,
+    pub panic: bool,
+}
+
+/// The result of running [`Bbq10Kbd::self_test`], a bring-up diagnostic
+/// exercising a known-good round trip through the device
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// The firmware version register was read successfully
+    pub version_read: bool,
+    /// The backlight register was read, written back unchanged, and
+    /// confirmed to read back the same value
+    pub backlight_roundtrip: bool,
+    /// The key status register was read successfully
+    pub key_status_read: bool,
+    /// The key status register's fifo count was in the valid 0..=32 range
+    pub fifo_count_sane: bool,
+}
+
+impl SelfTestReport {
+    /// Whether every check in this report passed
+    pub fn all_passed(&self) -> bool {
+        self.version_read
+            && self.backlight_roundtrip
+            && self.key_status_read
+            && self.fifo_count_sane
+    }
+}
+
 pub(crate) mod register {
     pub(crate) const WRITE: u8 = 0x80;
 
     pub(crate) const VERSION: u8 = 0x01;
+    pub(crate) const CONFIG: u8 = 0x02;
+    pub(crate) const INT_STATUS: u8 = 0x03;
 
     pub(crate) const KEY_STATUS: u8 = 0x04;
     pub(crate) const BACKLIGHT: u8 = 0x05;
+    pub(crate) const DEBOUNCE: u8 = 0x06;
+    pub(crate) const POLL_FREQUENCY: u8 = 0x07;
     pub(crate) const RESET: u8 = 0x08;
     pub(crate) const FIFO: u8 = 0x09;
+    pub(crate) const BACKLIGHT2: u8 = 0x0A;
+
+    pub(crate) const GPIO_DIR: u8 = 0x0B;
+    pub(crate) const GPIO_PULL_EN: u8 = 0x0C;
+    pub(crate) const GPIO_PULL_DIR: u8 = 0x0D;
+    pub(crate) const GPIO_VALUE: u8 = 0x0E;
+    pub(crate) const GPIO_INT_CONFIG: u8 = 0x0F;
+    pub(crate) const GPIO_INT_STATUS: u8 = 0x10;
+
+    pub(crate) const ADDRESS_CHANGE: u8 = 0x11;
+}
+
+mod config_bit {
+    pub(crate) const KEY_INT: u8 = 0b0000_0001;
+    pub(crate) const OVERFLOW_INT: u8 = 0b0000_0010;
+    pub(crate) const CAPSLOCK_INT: u8 = 0b0000_0100;
+    pub(crate) const NUMLOCK_INT: u8 = 0b0000_1000;
+    pub(crate) const PANIC_INT: u8 = 0b0001_0000;
+    pub(crate) const RAW_REPORT_MODE: u8 = 0b0010_0000;
+    pub(crate) const USE_MODIFIERS: u8 = 0b0100_0000;
+}
+
+mod int_bit {
+    pub(crate) const KEY: u8 = 0b0000_0001;
+    pub(crate) const OVERFLOW: u8 = 0b0000_0010;
+    pub(crate) const CAPSLOCK: u8 = 0b0000_0100;
+    pub(crate) const NUMLOCK: u8 = 0b0000_1000;
+    pub(crate) const PANIC: u8 = 0b0001_0000;
 }
 
 impl<I2C> Bbq10Kbd<I2C>
 where
     I2C: Read + Write,
 {
-    /// Create a new BBQ10 Keyboard instance
+    /// Create a new BBQ10 Keyboard instance at the default I2C address
     pub fn new(i2c: I2C) -> Self {
-        Self { i2c }
+        Self::new_with_address(i2c, KBD_ADDR)
+    }
+
+    /// Create a new BBQ10 Keyboard instance at a given I2C address
+    ///
+    /// Use this if the keyboard's address has already been changed from the
+    /// default via [`Bbq10Kbd::set_address`], e.g. to share a bus with a
+    /// second keyboard.
+    pub fn new_with_address(i2c: I2C, address: u8) -> Self {
+        Self { i2c, address }
+    }
+
+    /// Get the I2C address this instance currently talks to
+    pub fn address(&self) -> u8 {
+        self.address
     }
 
     /// Consume self, returning the inner I2C device
@@ -130,17 +269,35 @@ where
         self.i2c
     }
 
+    /// Change the keyboard's I2C address
+    ///
+    /// This writes the new address to the firmware's address-change
+    /// register, then updates the address stored in `self` to match. Future
+    /// calls on this instance will use the new address.
+    pub fn set_address(&mut self, new: u8) -> Result<()> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::ADDRESS_CHANGE | register::WRITE;
+        buf[1] = new;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
+
+        self.address = new;
+
+        Ok(())
+    }
+
     /// Get the version reported by the keyboard's firmware
     pub fn get_version(&mut self) -> Result<Version> {
         let mut buf = [0u8; 1];
 
         buf[0] = register::VERSION;
 
-        self.i2c.write(KBD_ADDR, &buf).map_err(|_| Error::I2c)?;
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
 
         buf[0] = 0;
 
-        self.i2c.read(KBD_ADDR, &mut buf).map_err(|_| Error::I2c)?;
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
 
         let val = buf[0];
 
@@ -154,27 +311,66 @@ where
         buf[0] = register::FIFO;
 
         self.i2c
-            .write(KBD_ADDR, &buf[..1])
+            .write(self.address, &buf[..1])
             .map_err(|_| Error::I2c)?;
 
         buf[0] = 0;
 
-        self.i2c.read(KBD_ADDR, &mut buf).map_err(|_| Error::I2c)?;
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
 
         Ok(KeyRaw::from_bytes(buf))
     }
 
+    /// Obtain a single fifo item, decoded through the given [`Keymap`]
+    ///
+    /// See [`keymap`](crate::keymap) for details on the decoding performed.
+    pub fn get_fifo_key_event(&mut self, keymap: &mut Keymap<'_>) -> Result<KeyEvents> {
+        let raw = self.get_fifo_key_raw()?;
+
+        Ok(keymap.decode(raw))
+    }
+
+    /// Drain the whole FIFO in one logical sweep into `buf`, returning the
+    /// number of events read
+    ///
+    /// This first reads [`KeyStatus::fifo_count`] to find out how many
+    /// events are queued, then reads exactly that many slots (up to
+    /// `buf.len()`). Because [`FifoCount::EmptyOr32`] is ambiguous between
+    /// zero and 32 queued events, it is treated conservatively as 32; either
+    /// way, draining stops early if [`KeyRaw::Invalid`] is observed.
+    pub fn read_events(&mut self, buf: &mut [KeyRaw]) -> Result<usize> {
+        let available = match self.get_key_status()?.fifo_count {
+            FifoCount::Known(n) => n as usize,
+            FifoCount::EmptyOr32 => 32,
+        };
+
+        let mut count = 0;
+
+        for slot in buf.iter_mut().take(available) {
+            let raw = self.get_fifo_key_raw()?;
+
+            if raw == KeyRaw::Invalid {
+                break;
+            }
+
+            *slot = raw;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
     /// Get the current level of backlight. All u8 values are valid
     pub fn get_backlight(&mut self) -> Result<u8> {
         let mut buf = [0u8; 1];
 
         buf[0] = register::BACKLIGHT;
 
-        self.i2c.write(KBD_ADDR, &buf).map_err(|_| Error::I2c)?;
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
 
         buf[0] = 0;
 
-        self.i2c.read(KBD_ADDR, &mut buf).map_err(|_| Error::I2c)?;
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
 
         Ok(buf[0])
     }
@@ -186,7 +382,84 @@ where
         buf[0] = register::BACKLIGHT | register::WRITE;
         buf[1] = level;
 
-        self.i2c.write(KBD_ADDR, &buf).map_err(|_| Error::I2c)
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)
+    }
+
+    /// Get the current level of the secondary backlight. All u8 values are
+    /// valid
+    pub fn get_backlight2(&mut self) -> Result<u8> {
+        let mut buf = [0u8; 1];
+
+        buf[0] = register::BACKLIGHT2;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
+
+        buf[0] = 0;
+
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
+
+        Ok(buf[0])
+    }
+
+    /// Set the current level of the secondary backlight. All u8 values are
+    /// valid
+    pub fn set_backlight2(&mut self, level: u8) -> Result<()> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::BACKLIGHT2 | register::WRITE;
+        buf[1] = level;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)
+    }
+
+    /// Get the current key debounce time
+    pub fn get_debounce(&mut self) -> Result<Debounce> {
+        let mut buf = [0u8; 1];
+
+        buf[0] = register::DEBOUNCE;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
+
+        buf[0] = 0;
+
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
+
+        Ok(Debounce(buf[0]))
+    }
+
+    /// Set the key debounce time
+    pub fn set_debounce(&mut self, debounce: Debounce) -> Result<()> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::DEBOUNCE | register::WRITE;
+        buf[1] = debounce.0;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)
+    }
+
+    /// Get the current matrix scan poll frequency
+    pub fn get_poll_frequency(&mut self) -> Result<PollFrequency> {
+        let mut buf = [0u8; 1];
+
+        buf[0] = register::POLL_FREQUENCY;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
+
+        buf[0] = 0;
+
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
+
+        Ok(PollFrequency(buf[0]))
+    }
+
+    /// Set the matrix scan poll frequency
+    pub fn set_poll_frequency(&mut self, frequency: PollFrequency) -> Result<()> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::POLL_FREQUENCY | register::WRITE;
+        buf[1] = frequency.0;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)
     }
 
     /// Reset the device via software
@@ -199,7 +472,7 @@ where
         buf[0] = register::RESET;
 
         // This is enough to reset the device
-        self.i2c.write(KBD_ADDR, &buf).map_err(|_| Error::I2c)
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)
     }
 
     /// Get the reported status of the keyboard
@@ -208,14 +481,101 @@ where
 
         buf[0] = register::KEY_STATUS;
 
-        self.i2c.write(KBD_ADDR, &buf).map_err(|_| Error::I2c)?;
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
 
         buf[0] = 0;
 
-        self.i2c.read(KBD_ADDR, &mut buf).map_err(|_| Error::I2c)?;
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
 
         Ok(KeyStatus::from_byte(buf[0]))
     }
+
+    /// Get the keyboard's current interrupt/report configuration
+    pub fn get_config(&mut self) -> Result<Config> {
+        let mut buf = [0u8; 1];
+
+        buf[0] = register::CONFIG;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
+
+        buf[0] = 0;
+
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
+
+        Ok(Config::from_byte(buf[0]))
+    }
+
+    /// Set the keyboard's interrupt/report configuration
+    pub fn set_config(&mut self, config: Config) -> Result<()> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::CONFIG | register::WRITE;
+        buf[1] = config.to_byte();
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)
+    }
+
+    /// Get which condition(s) have latched the interrupt line
+    pub fn get_int_status(&mut self) -> Result<IntStatus> {
+        let mut buf = [0u8; 1];
+
+        buf[0] = register::INT_STATUS;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)?;
+
+        buf[0] = 0;
+
+        self.i2c.read(self.address, &mut buf).map_err(|_| Error::I2c)?;
+
+        Ok(IntStatus::from_byte(buf[0]))
+    }
+
+    /// Clear the interrupt status register, de-asserting the IRQ line
+    ///
+    /// Call this only after draining the FIFO, or a key event queued
+    /// between the read and the clear may be lost.
+    pub fn clear_int_status(&mut self) -> Result<()> {
+        let mut buf = [0u8; 2];
+
+        buf[0] = register::INT_STATUS | register::WRITE;
+        buf[1] = 0;
+
+        self.i2c.write(self.address, &buf).map_err(|_| Error::I2c)
+    }
+
+    /// Exercise a known round trip through the device, useful for verifying
+    /// wiring before relying on the keyboard
+    ///
+    /// This never returns an error on its own; instead, each check's result
+    /// is reported individually so a caller can see exactly what passed.
+    pub fn self_test(&mut self) -> SelfTestReport {
+        let version_read = self.get_version().is_ok();
+
+        let backlight_roundtrip = self
+            .get_backlight()
+            .and_then(|original| {
+                self.set_backlight(original)?;
+                Ok(self.get_backlight()? == original)
+            })
+            .unwrap_or(false);
+
+        let key_status = self.get_key_status();
+        let key_status_read = key_status.is_ok();
+        let fifo_count_sane = matches!(
+            key_status,
+            Ok(KeyStatus {
+                fifo_count: FifoCount::Known(0..=32) | FifoCount::EmptyOr32,
+                ..
+            })
+        );
+
+        SelfTestReport {
+            version_read,
+            backlight_roundtrip,
+            key_status_read,
+            fifo_count_sane,
+        }
+    }
 }
 
 impl Version {
@@ -238,6 +598,60 @@ impl KeyRaw {
     }
 }
 
+impl Config {
+    pub(crate) fn from_byte(byte: u8) -> Self {
+        Self {
+            key_interrupt: (byte & config_bit::KEY_INT) != 0,
+            overflow_interrupt: (byte & config_bit::OVERFLOW_INT) != 0,
+            capslock_interrupt: (byte & config_bit::CAPSLOCK_INT) != 0,
+            numlock_interrupt: (byte & config_bit::NUMLOCK_INT) != 0,
+            panic_interrupt: (byte & config_bit::PANIC_INT) != 0,
+            raw_report_mode: (byte & config_bit::RAW_REPORT_MODE) != 0,
+            use_modifiers: (byte & config_bit::USE_MODIFIERS) != 0,
+        }
+    }
+
+    pub(crate) fn to_byte(self) -> u8 {
+        let mut byte = 0u8;
+
+        if self.key_interrupt {
+            byte |= config_bit::KEY_INT;
+        }
+        if self.overflow_interrupt {
+            byte |= config_bit::OVERFLOW_INT;
+        }
+        if self.capslock_interrupt {
+            byte |= config_bit::CAPSLOCK_INT;
+        }
+        if self.numlock_interrupt {
+            byte |= config_bit::NUMLOCK_INT;
+        }
+        if self.panic_interrupt {
+            byte |= config_bit::PANIC_INT;
+        }
+        if self.raw_report_mode {
+            byte |= config_bit::RAW_REPORT_MODE;
+        }
+        if self.use_modifiers {
+            byte |= config_bit::USE_MODIFIERS;
+        }
+
+        byte
+    }
+}
+
+impl IntStatus {
+    pub(crate) fn from_byte(byte: u8) -> Self {
+        Self {
+            key: (byte & int_bit::KEY) != 0,
+            overflow: (byte & int_bit::OVERFLOW) != 0,
+            capslock: (byte & int_bit::CAPSLOCK) != 0,
+            numlock: (byte & int_bit::NUMLOCK) != 0,
+            panic: (byte & int_bit::PANIC) != 0,
+        }
+    }
+}
+
 impl KeyStatus {
     pub(crate) fn from_byte(mut byte: u8) -> Self {
         let num_lock = if (byte & 0b0100_0000) != 0 {